@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, Weak};
 
 use crate::data_source::DataSource;
 
@@ -13,7 +14,8 @@ struct MapEntry<'a> {
     offset: usize,
     span: usize,
     addr: usize,
-    flags: FlagBuilder
+    flags: FlagBuilder,
+    node: Arc<MapNode>, // our spot in the fork/COW tree; see `MapNode`.
 }
 
 impl<'a> MapEntry<'a> {
@@ -25,15 +27,145 @@ impl<'a> MapEntry<'a> {
             span,
             addr,
             flags,
+            node: MapNode::new(addr),
         }
     }
+
+    /// This mapping's own `flags`, minus whatever rights an ancestor's
+    /// `flush_rights`/`unmap_and_propagate` has since revoked via `node`.
+    /// Anything that decides whether an access is allowed (as opposed to
+    /// code that just wants to know how the mapping was originally
+    /// created) should check this instead of `flags` directly.
+    fn effective_flags(&self) -> FlagBuilder {
+        self.flags.but_not(*self.node.revoked.lock().unwrap())
+    }
+}
+
+/// A node in the fork/COW "mapping database" tree, modeled on the recursive
+/// mapping-database idea from L4's `map_util`. Every `MapEntry` owns one of
+/// these. A freshly-created mapping gets a fresh, childless node; `fork`
+/// gives the child's copy of a mapping a new node and links it underneath
+/// the parent's, so that a rights change pushed into the parent (`unmap`,
+/// a permission flush) can be walked down to every space descended from it
+/// *without* needing direct access to those spaces — we just need their
+/// `MapNode`s.
+struct MapNode {
+    addr: VirtualAddress,
+    // Rights that have been revoked from this node (and therefore this
+    // mapping) by an ancestor's rights-reduction, over and above whatever
+    // the mapping's own `FlagBuilder` says. See `AddressSpace::flush_rights`.
+    revoked: Mutex<FlagBuilder>,
+    children: Mutex<Vec<Arc<MapNode>>>,
+    // The node (if any) whose `children` holds this one — i.e. the node
+    // `fork` linked us underneath. `fork` sets this when it creates the
+    // node; `AddressSpace::merge_into` also sets it on the surviving node
+    // if that node had none and the merged-away one did. We keep this
+    // around purely so `merge_into` can find and fix up that reference when
+    // this node's mapping is merged away, instead of leaving the parent's
+    // `children` entry pointing at a node no live mapping uses anymore.
+    parent: Mutex<Option<Weak<MapNode>>>,
+}
+
+impl MapNode {
+    fn new(addr: VirtualAddress) -> Arc<MapNode> {
+        Arc::new(MapNode {
+            addr,
+            revoked: Mutex::new(FlagBuilder::new()),
+            children: Mutex::new(Vec::new()),
+            parent: Mutex::new(None),
+        })
+    }
 }
 
 
 /// An address space. Can't live longer than the MapEntries in it?
 pub struct AddressSpace<'b>{
     name: String,
-    mappings: Vec<MapEntry<'b>>, // see below for comments
+    mappings: BTreeMap<VirtualAddress, MapEntry<'b>>, // keyed by the mapping's starting addr
+    free_list: BTreeMap<VirtualAddress, usize>, // keyed by start addr of a free span -> its length
+    reuse_pool: Option<ReusePool>, // opt-in quarantine for freed ranges; see `ReusePool`.
+}
+
+/// A tiny xorshift64 PRNG. We only need this to make `ReusePool`'s reuse
+/// decisions reproducible from a seed for testing; pulling in the `rand`
+/// crate for that would be overkill (and this needs to stay `#no_std`
+/// compatible, per the note below).
+struct Xorshift64 {
+    state: u64,
+}
+
+impl Xorshift64 {
+    fn new(seed: u64) -> Self {
+        // xorshift is undefined at an all-zero state, so nudge a zero seed.
+        Self { state: if seed == 0 { 0xdead_beef_cafe_babe } else { seed } }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// True with probability `numerator / denominator`.
+    fn chance(&mut self, numerator: u64, denominator: u64) -> bool {
+        self.next_u64() % denominator < numerator
+    }
+}
+
+/// Bound on how many freed ranges `ReusePool` holds onto before evicting
+/// the oldest one back to general free space.
+const REUSE_POOL_CAPACITY: usize = 16;
+/// How often `ReusePool::take` hands a quarantined range back out, expressed
+/// as a (numerator, denominator) chance.
+const REUSE_PROBABILITY: (u64, u64) = (1, 4);
+
+/// An opt-in quarantine for freed virtual ranges, adapted from Miri's
+/// address-reuse-pool. Rather than handing a freshly-unmapped range straight
+/// back out to the next `add_mapping`, we hold onto a bounded number of
+/// recently-freed `(addr, span)` ranges and only reuse one some of the time.
+/// That way a stale pointer into a just-unmapped region doesn't reliably
+/// land on freshly-remapped memory, which turns stale-mapping-reuse bugs
+/// into something testing can actually catch instead of them "working" by
+/// accident most of the time.
+struct ReusePool {
+    rng: Xorshift64,
+    // most-recently-freed ranges, oldest first; bounded to `REUSE_POOL_CAPACITY` entries.
+    pool: Vec<(VirtualAddress, usize)>,
+}
+
+impl ReusePool {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Xorshift64::new(seed),
+            pool: Vec::new(),
+        }
+    }
+
+    /// Quarantine a freed range instead of letting it go straight back to
+    /// the free list. If the pool was already full, the oldest entry falls
+    /// out and is returned so the caller can return *it* to general free
+    /// space instead.
+    fn push(&mut self, addr: VirtualAddress, span: usize) -> Option<(VirtualAddress, usize)> {
+        self.pool.push((addr, span));
+        if self.pool.len() > REUSE_POOL_CAPACITY {
+            return Some(self.pool.remove(0));
+        }
+        None
+    }
+
+    /// With probability `REUSE_PROBABILITY`, pull a pooled range of at
+    /// least `needed` bytes back out (removing it from the pool) for
+    /// `add_mapping` to hand out again.
+    fn take(&mut self, needed: usize) -> Option<(VirtualAddress, usize)> {
+        if !self.rng.chance(REUSE_PROBABILITY.0, REUSE_PROBABILITY.1) {
+            return None;
+        }
+        let idx = self.pool.iter().position(|&(_, span)| span >= needed)?;
+        Some(self.pool.remove(idx))
+    }
 }
 
 // comments about storing mappings
@@ -47,23 +179,53 @@ pub struct AddressSpace<'b>{
 // from a crate (but remember it needs to be #no_std compatible), or even write your own.
 // See this ticket from Riley: https://github.com/dylanmc/cs393_vm_api/issues/10
 
+// UPDATE: we switched `mappings` from a `Vec` that got linearly scanned and
+// re-sorted on every insert to a `BTreeMap<addr, MapEntry>`, which keeps
+// mappings ordered by address for free and gives us O(log n) insert/remove/
+// lookup-by-exact-address. Available address space is tracked separately in
+// `free_list`, a coalescing free-list allocator (same idea as a classic
+// malloc free-list, just over virtual address ranges instead of heap bytes):
+// each entry is a `(start, len)` free span; `add_mapping` does first-fit over
+// it and `add_mapping_at` looks up the free span containing `start`, rather
+// than re-deriving gaps from neighboring mappings every time.
+
 impl<'c> AddressSpace<'c> {
     #[must_use]
     pub fn new(name: &str) -> Self {
+        let mut free_list = BTreeMap::new();
+        // Everything from PAGE_SIZE to VADDR_MAX starts out free; we never
+        // hand out page 0.
+        free_list.insert(PAGE_SIZE, VADDR_MAX - PAGE_SIZE);
         Self {
             name: name.to_string(),
-            mappings: Vec::new(), // <- here I changed from LinkedList, for reasons
+            mappings: BTreeMap::new(),
+            free_list,
+            reuse_pool: None,
         } // I encourage you to try other sparse representations - trees, DIY linked lists, ...
     }
 
+    /// Like `new`, but freed virtual ranges are quarantined in a `ReusePool`
+    /// before they're made available again, so stale-mapping-reuse bugs
+    /// don't reliably "just work". `seed` makes the reuse decisions
+    /// reproducible, which is the point: tests that want this quarantining
+    /// behavior should use this constructor instead of `new` so a failure
+    /// reproduces the same way every run.
+    #[must_use]
+    pub fn with_reuse_seed(name: &str, seed: u64) -> Self {
+        Self {
+            reuse_pool: Some(ReusePool::new(seed)),
+            ..Self::new(name)
+        }
+    }
+
     /// Add a mapping from a `DataSource` into this `AddressSpace`.
     ///
     /// # Errors
     /// If the desired mapping is invalid.
     /// TODO: how does our test in lib.rs succeed?
     /// ANSWER: The test in lib.rs makes two mapppings — one at addr 0 of span 1, the other at addr PAGE_SIZE with span 0.
-    /// The test asserts that the first mapping doesn't return 0, which is true because we return addr_iter + PAGE_SIZE, 
-    /// which = 2*PAGE_SIZE when offset is 0 and span is 1. The second mapping 
+    /// The test asserts that the first mapping doesn't return 0, which is true because we return free_start + PAGE_SIZE (the first free span starts at PAGE_SIZE),
+    /// which = 2*PAGE_SIZE when offset is 0 and span is 1. The second mapping
     // pub fn add_mapping<'a, D: DataSource + 'a>(
     //     &'a mut self,
     pub fn add_mapping<D: DataSource + 'c>(
@@ -73,28 +235,43 @@ impl<'c> AddressSpace<'c> {
         span: usize,
         flags: FlagBuilder,
     ) -> Result<VirtualAddress, &str> {
-        let mut addr_iter = PAGE_SIZE; // let's not map page 0. addr_iter our running placeholder for where there might be space in the memory.
-        let mut gap;
-        for mapping in &self.mappings { // look to the next mapping
-            gap = mapping.addr - addr_iter; // difference between next mapping & current empty space
-            if gap > span + 2 * PAGE_SIZE { // can fit this mapping (span) with empty page each side
-                break;
-            }
-            addr_iter = mapping.addr + mapping.span; // couldn't fit between current guess and this mapping, try next guess at the end of this mapping
-            // ROUND UP TO THE NEAREST PAGE
-            if addr_iter % PAGE_SIZE != 0 {
-                let multiples: usize = addr_iter / PAGE_SIZE;
-                addr_iter = (multiples + 1) * PAGE_SIZE;
+        // Give the reuse pool first crack at satisfying this request: if it
+        // hands back a range, it's already got guard space around it from
+        // when it was originally carved out, so we can map straight into it.
+        if let Some((addr, pooled_span)) = self.reuse_pool.as_mut().and_then(|pool| pool.take(span)) {
+            if pooled_span > span {
+                // only the front `span` bytes are spoken for; the rest goes
+                // straight back to general free space.
+                self.free_span(addr + span, pooled_span - span);
             }
+            let new_mapping: MapEntry = MapEntry::new(source, offset, span, addr, flags);
+            self.mappings.insert(addr, new_mapping);
+            self.try_merge_neighbors(addr);
+            return Ok(addr);
         }
-        if addr_iter + span + 2 * PAGE_SIZE < VADDR_MAX { // 1 blank page on either side. Span for how much this mapping needs. addr_iter for where it can go
-            let mapping_addr = addr_iter + PAGE_SIZE; // 1 blank page before.
-            let new_mapping: MapEntry = MapEntry::new(source, offset, span, mapping_addr, flags);
-            self.mappings.push(new_mapping); // add new mapping to end
-            self.mappings.sort_by(|a, b| a.addr.cmp(&b.addr)); // put it in order of addresses
-            return Ok(mapping_addr); // no error, result type of usize (called VirtualAddress)
-        }
-        Err("out of address space!")
+
+        // First-fit: walk the free list in address order (BTreeMap iterates
+        // by key) and take the first span with enough room for `span` plus
+        // one guard page on either side.
+        let needed = span + 2 * PAGE_SIZE;
+        let found = self
+            .free_list
+            .iter()
+            .find(|(_, &len)| len >= needed)
+            .map(|(&start, &len)| (start, len));
+
+        let Some((free_start, free_len)) = found else {
+            return Err("out of address space!");
+        };
+
+        let mapping_addr = free_start + PAGE_SIZE; // 1 blank page before.
+        self.free_list.remove(&free_start);
+        self.split_free_span(free_start, free_len, mapping_addr, span);
+
+        let new_mapping: MapEntry = MapEntry::new(source, offset, span, mapping_addr, flags);
+        self.mappings.insert(mapping_addr, new_mapping);
+        self.try_merge_neighbors(mapping_addr);
+        Ok(mapping_addr) // no error, result type of usize (called VirtualAddress)
     }
 
     /// Add a mapping from `DataSource` into this `AddressSpace` starting at a specific address.
@@ -109,23 +286,139 @@ impl<'c> AddressSpace<'c> {
         start: VirtualAddress,
         flags: FlagBuilder
     ) -> Result<(), &str> {
-        // check whether there's space for mapping
-        let mut next_mapping: usize = 0;
-        for mapping in &self.mappings {
-            next_mapping = mapping.addr;
-            if next_mapping > start {
-                break;
+        // `start` has to fall inside a single free span, with at least a
+        // leading guard page between it and the free span's start (mirroring
+        // the leading guard `add_mapping` always carves out) and enough room
+        // left in that span (from `start` onward) for `span` plus a
+        // trailing guard page.
+        let found = self
+            .free_list
+            .range(..=start)
+            .next_back()
+            .filter(|&(&free_start, &free_len)| {
+                start >= free_start + PAGE_SIZE && start + span + PAGE_SIZE <= free_start + free_len
+            })
+            .map(|(&free_start, &free_len)| (free_start, free_len));
+
+        let Some((free_start, free_len)) = found else {
+            return Err("Not enough space after 'start' to map here.");
+        };
+
+        self.free_list.remove(&free_start);
+        self.split_free_span(free_start, free_len, start, span);
+
+        let new_mapping: MapEntry = MapEntry::new(source, offset, span, start, flags);
+        self.mappings.insert(start, new_mapping);
+        self.try_merge_neighbors(start);
+        Ok(())
+    }
+
+    /// Carve `[mapping_addr, mapping_addr + span)` out of the free span
+    /// `[free_start, free_start + free_len)` and put whatever's left over on
+    /// either side back into `self.free_list`.
+    ///
+    /// Assumes `free_start` has already been removed from `self.free_list`
+    /// and that the carved-out region fits inside the free span.
+    fn split_free_span(&mut self, free_start: usize, free_len: usize, mapping_addr: usize, span: usize) {
+        let free_end = free_start + free_len;
+        if mapping_addr > free_start {
+            // leading leftover becomes (or stays) free; it doubles as the
+            // guard page before this mapping.
+            self.free_list.insert(free_start, mapping_addr - free_start);
+        }
+        let mapping_end = mapping_addr + span;
+        if mapping_end < free_end {
+            // trailing leftover, doubling as the guard page after this
+            // mapping.
+            self.free_list.insert(mapping_end, free_end - mapping_end);
+        }
+    }
+
+    /// Try to merge the mapping at `addr` with its immediate neighbors in
+    /// address order, the way Linux's `mm/mmap.c` merges VMAs: two mappings
+    /// merge when they're contiguous in address (`a.addr + a.span ==
+    /// b.addr`), back the same `DataSource` with contiguous offsets
+    /// (`a.offset + a.span == b.offset`), and carry equal `FlagBuilder`s.
+    /// Checks both the preceding and following neighbor, so a chain of
+    /// three-or-more newly-adjacent mappings folds into one in a single
+    /// call. Returns whether anything merged.
+    pub fn try_merge_neighbors(&mut self, mut addr: VirtualAddress) -> bool {
+        let mut merged = false;
+
+        if let Some(prev_addr) = self.mappings.range(..addr).next_back().map(|(&a, _)| a) {
+            if self.mergeable(prev_addr, addr) {
+                self.merge_into(prev_addr, addr);
+                addr = prev_addr;
+                merged = true;
             }
         }
-        if start + span + 2*PAGE_SIZE < next_mapping {  // there's space! 
-            let new_mapping: MapEntry = MapEntry::new(source, offset, span, start, flags);
-            self.mappings.push(new_mapping); // add new mapping to end
-            self.mappings.sort_by(|a, b| a.addr.cmp(&b.addr)); // put it in order of addresses
-            Ok(())
-        } else {
-            Err("Not enough space after 'start' to map here.")
+
+        if let Some(next_addr) = self.mappings.range(addr + 1..).next().map(|(&a, _)| a) {
+            if self.mergeable(addr, next_addr) {
+                self.merge_into(addr, next_addr);
+                merged = true;
+            }
         }
 
+        merged
+    }
+
+    /// Would the mappings at `left_addr` and `right_addr` merge into one?
+    fn mergeable(&self, left_addr: VirtualAddress, right_addr: VirtualAddress) -> bool {
+        let (Some(left), Some(right)) = (self.mappings.get(&left_addr), self.mappings.get(&right_addr)) else {
+            return false;
+        };
+        left.addr + left.span == right.addr
+            && Arc::ptr_eq(&left.source, &right.source)
+            && left.offset + left.span == right.offset
+            && left.flags == right.flags
+    }
+
+    /// Absorb the mapping at `right_addr` into the one at `left_addr` by
+    /// extending the left entry's span. Callers must have already checked
+    /// `mergeable(left_addr, right_addr)`.
+    ///
+    /// `right`'s `MapNode` may be the root of its own fork/COW subtree (if
+    /// this mapping was ever `fork()`'d), so we can't just drop it along
+    /// with `right` — that would strand those descendants where no future
+    /// `flush_rights`/`unmap_and_propagate` call could ever reach them
+    /// again. Instead fold `right.node` into `left.node`: move its children
+    /// over so they're still walked from the merged mapping's node, carry
+    /// over anything already revoked on `right.node` so the combined
+    /// mapping doesn't regain rights an ancestor had taken away, and — if
+    /// `right.node` was itself linked into *its* parent's `children` (i.e.
+    /// this mapping was the product of an earlier `fork`), redirect that
+    /// parent's reference from `right.node` over to `left.node` so a later
+    /// rights-flush issued from up there still reaches the merged mapping
+    /// instead of silently hitting a node nothing uses anymore.
+    fn merge_into(&mut self, left_addr: VirtualAddress, right_addr: VirtualAddress) {
+        let right = self.mappings.remove(&right_addr).expect("merge_into: right_addr must exist");
+        let left = self.mappings.get_mut(&left_addr).expect("merge_into: left_addr must exist");
+        left.span += right.span;
+
+        let mut right_children = right.node.children.lock().unwrap();
+        left.node.children.lock().unwrap().append(&mut right_children);
+        drop(right_children);
+
+        let right_revoked = *right.node.revoked.lock().unwrap();
+        let mut left_revoked = left.node.revoked.lock().unwrap();
+        *left_revoked = left_revoked.and(right_revoked);
+        drop(left_revoked);
+
+        let right_parent = right.node.parent.lock().unwrap().clone();
+        if let Some(parent) = right_parent.and_then(|weak| weak.upgrade()) {
+            let mut parent_children = parent.children.lock().unwrap();
+            parent_children.retain(|child| !Arc::ptr_eq(child, &right.node));
+            if !parent_children.iter().any(|child| Arc::ptr_eq(child, &left.node)) {
+                parent_children.push(left.node.clone());
+            }
+            drop(parent_children);
+
+            let mut left_parent = left.node.parent.lock().unwrap();
+            if left_parent.is_none() {
+                *left_parent = Some(Arc::downgrade(&parent));
+            }
+        }
     }
 
     /// Remove the mapping to `DataSource` that starts at the given address.
@@ -137,14 +430,355 @@ impl<'c> AddressSpace<'c> {
         source: Arc<D>,
         start: VirtualAddress,
     ) -> Result<(), &str> {
-        // iterate through mappings, find the given address? remove that mapping?
-        for (mapping_num,mapping) in (&self.mappings).iter().enumerate() {
-            if mapping.addr == start {
-                self.mappings.remove(mapping_num);
-                return Ok(());
+        let Some(mapping) = self.mappings.remove(&start) else {
+            return Err("no mapping found starting at that address.");
+        };
+        drop(source); // caller passes this in to name which source they mean to unmap; we don't need it once we've found the mapping by address.
+        self.release_span(mapping.addr, mapping.span);
+        Ok(())
+    }
+
+    /// Release a freed `[addr, addr + span)` range: if a `reuse_pool` is
+    /// configured, quarantine it there instead of making it immediately
+    /// reusable (see `ReusePool`); otherwise (or if quarantining evicts an
+    /// older entry) return it straight to `self.free_list`.
+    fn release_span(&mut self, addr: VirtualAddress, span: usize) {
+        let evicted = match &mut self.reuse_pool {
+            Some(pool) => pool.push(addr, span),
+            None => Some((addr, span)),
+        };
+        if let Some((evicted_addr, evicted_span)) = evicted {
+            self.free_span(evicted_addr, evicted_span);
+        }
+    }
+
+    /// Return `[start, start + len)` to the free list, coalescing with
+    /// whatever free spans sit immediately before and/or after it so the
+    /// free list doesn't fragment into a pile of adjacent tiny entries.
+    fn free_span(&mut self, start: VirtualAddress, len: usize) {
+        let mut new_start = start;
+        let mut new_len = len;
+
+        // merge with the free span immediately before us, if any
+        if let Some((&before_start, &before_len)) = self.free_list.range(..start).next_back() {
+            if before_start + before_len == new_start {
+                self.free_list.remove(&before_start);
+                new_start = before_start;
+                new_len += before_len;
+            }
+        }
+
+        // merge with the free span immediately after us, if any
+        if let Some((&after_start, &after_len)) = self.free_list.range(new_start + new_len..).next() {
+            if new_start + new_len == after_start {
+                self.free_list.remove(&after_start);
+                new_len += after_len;
+            }
+        }
+
+        self.free_list.insert(new_start, new_len);
+    }
+
+    /// Fork this `AddressSpace`, producing a child that shares every
+    /// mapping with the parent, copy-on-write.
+    ///
+    /// Every writable parent mapping is re-flagged `cow` and `but_not`
+    /// `write` in *both* spaces (honoring `FlagBuilder::is_valid`'s
+    /// cow/write invariant — you can't have both at once), and the child's
+    /// copy of each mapping gets a new `MapNode` linked underneath the
+    /// parent's, so a later rights reduction in the parent (see
+    /// `flush_rights`) can find every descendant. Read-only mappings don't
+    /// need `cow`: nobody's going to write them, so there's nothing to copy.
+    #[must_use]
+    pub fn fork(&mut self) -> AddressSpace<'c> {
+        let mut child_mappings = BTreeMap::new();
+        for (&addr, mapping) in &mut self.mappings {
+            if mapping.flags.write {
+                mapping.flags = mapping.flags.and(FlagBuilder::cow()).but_not(FlagBuilder::write());
             }
+
+            let child_node = MapNode::new(addr);
+            *child_node.parent.lock().unwrap() = Some(Arc::downgrade(&mapping.node));
+            mapping.node.children.lock().unwrap().push(child_node.clone());
+
+            child_mappings.insert(addr, MapEntry {
+                source: mapping.source.clone(),
+                offset: mapping.offset,
+                span: mapping.span,
+                addr,
+                flags: mapping.flags,
+                node: child_node,
+            });
+        }
+
+        AddressSpace {
+            name: format!("{} (fork)", self.name),
+            mappings: child_mappings,
+            free_list: self.free_list.clone(),
+            reuse_pool: None,
+        }
+    }
+
+    /// Resolve a write fault against a `cow` mapping at `addr`: detach it
+    /// from the fork tree it was shared through (a fresh, childless
+    /// `MapNode` of its own, so this mapping stops receiving *future*
+    /// rights reductions pushed through the node it used to share) and
+    /// flip it from `cow` back to plain `write`. Whatever was already
+    /// revoked on the old node carries over to the new one — an ancestor's
+    /// past `flush_rights` shouldn't get silently undone just because the
+    /// child happened to take its own copy afterward. This is the "clone
+    /// just the faulting mapping" half of COW fault resolution — actually
+    /// duplicating the underlying page contents is the `DataSource`'s job,
+    /// not the address space's.
+    ///
+    /// # Errors
+    /// If there's no mapping at `addr`, or it isn't `cow`.
+    pub fn resolve_cow_fault(&mut self, addr: VirtualAddress) -> Result<(), &str> {
+        let Some(mapping) = self.mappings.get_mut(&addr) else {
+            return Err("no mapping found at that address");
+        };
+        if !mapping.flags.cow {
+            return Err("mapping is not copy-on-write");
         }
-        Err("no mapping found starting at that address.")
+        mapping.flags = mapping.flags.toggle_cow().toggle_write();
+        let already_revoked = *mapping.node.revoked.lock().unwrap();
+        let new_node = MapNode::new(mapping.addr);
+        *new_node.revoked.lock().unwrap() = already_revoked;
+        mapping.node = new_node;
+        Ok(())
+    }
+
+    /// Remove `remove_flags` from the mapping at `addr`, then push that same
+    /// rights reduction down to every mapping descended from it via `fork`
+    /// (the `v_delete` flush-rights recursion), without needing direct
+    /// access to the descendants' `AddressSpace`s — we only need their
+    /// `MapNode`s. Returns the set of affected child addresses; this
+    /// space's own address is not included.
+    ///
+    /// # Errors
+    /// If there's no mapping at `addr`.
+    pub fn flush_rights(&mut self, addr: VirtualAddress, remove_flags: FlagBuilder) -> Result<Vec<VirtualAddress>, &str> {
+        let Some(mapping) = self.mappings.get_mut(&addr) else {
+            return Err("no mapping found at that address");
+        };
+        mapping.flags = mapping.flags.but_not(remove_flags);
+        let mut affected = Vec::new();
+        Self::flush_rights_recursive(&mapping.node, remove_flags, &mut affected);
+        Ok(affected)
+    }
+
+    /// Unmap the mapping at `addr` and propagate that removal down the
+    /// `fork` COW tree: every mapping derived from it, in every descendant
+    /// `AddressSpace`, has all of its rights flushed away. Returns the
+    /// addresses of every derived mapping that was affected.
+    ///
+    /// # Errors
+    /// If there's no mapping starting at `addr`.
+    pub fn unmap_and_propagate(&mut self, addr: VirtualAddress) -> Result<Vec<VirtualAddress>, &str> {
+        let Some(mapping) = self.mappings.remove(&addr) else {
+            return Err("no mapping found starting at that address.");
+        };
+        self.release_span(mapping.addr, mapping.span);
+        let all_rights = FlagBuilder::read().and(FlagBuilder::write()).and(FlagBuilder::execute());
+        let mut affected = Vec::new();
+        Self::flush_rights_recursive(&mapping.node, all_rights, &mut affected);
+        Ok(affected)
+    }
+
+    fn flush_rights_recursive(node: &Arc<MapNode>, remove_flags: FlagBuilder, affected: &mut Vec<VirtualAddress>) {
+        for child in node.children.lock().unwrap().iter() {
+            let mut revoked = child.revoked.lock().unwrap();
+            *revoked = revoked.and(remove_flags);
+            drop(revoked);
+            affected.push(child.addr);
+            Self::flush_rights_recursive(child, remove_flags, affected);
+        }
+    }
+
+    /// Unmap `[start, start + len)`, splitting or trimming any mapping that
+    /// only partially overlaps the range, the way Linux's `mm/mmap.c` does
+    /// for `munmap`. A mapping entirely inside the range is removed
+    /// outright; one overlapping just one edge is shortened in place; a
+    /// range that punches a hole in the middle of a mapping splits it into
+    /// two `MapEntry`s sharing the same `DataSource`, with offsets adjusted
+    /// so each still points at the bytes it used to.
+    ///
+    /// A mapping that's removed outright has its rights flushed down its
+    /// fork/COW subtree exactly like `unmap_and_propagate` does (a span
+    /// this address space no longer considers mapped can't be left fully
+    /// readable/writable in a descendant — especially once `ReusePool`
+    /// hands the freed range back out to someone else). A mapping that only
+    /// gets trimmed keeps its own node — and thus its fork/COW descendants
+    /// reachable — on every surviving fragment; a hole punched through the
+    /// middle leaves both fragments sharing the same node rather than
+    /// stranding one of them, since there's no sub-range finer than "this
+    /// node's mapping" to split the revocation tracking against anyway.
+    /// Returns the addresses of every derived mapping that was affected by
+    /// one of those full removals.
+    pub fn unmap(&mut self, start: VirtualAddress, len: usize) -> Vec<VirtualAddress> {
+        let end = start + len;
+        // Grab the starts of every overlapping mapping first — mutating
+        // `self.mappings` while iterating it directly would fight the
+        // borrow checker.
+        let overlapping: Vec<VirtualAddress> = self
+            .mappings
+            .range(..end)
+            .filter(|(_, mapping)| mapping.addr + mapping.span > start)
+            .map(|(&addr, _)| addr)
+            .collect();
+
+        let all_rights = FlagBuilder::read().and(FlagBuilder::write()).and(FlagBuilder::execute());
+        let mut affected = Vec::new();
+
+        for addr in overlapping {
+            let mapping = self.mappings.remove(&addr).unwrap();
+            let m_start = mapping.addr;
+            let m_end = mapping.addr + mapping.span;
+            let left_survives = m_start < start;
+            let right_survives = m_end > end;
+            // Every surviving fragment keeps a reference to the original
+            // node — if a hole was punched in the middle, producing two
+            // fragments, both fragments clone the same `Arc<MapNode>`
+            // rather than one of them stranding any fork/COW descendants
+            // with no way back to it.
+            let node = mapping.node;
+
+            // the part (if any) of the mapping to the left of the punched
+            // range survives as-is, at its original offset.
+            if left_survives {
+                let span = start - m_start;
+                self.mappings.insert(m_start, MapEntry {
+                    source: mapping.source.clone(),
+                    offset: mapping.offset,
+                    span,
+                    addr: m_start,
+                    flags: mapping.flags,
+                    node: node.clone(),
+                });
+                self.try_merge_neighbors(m_start);
+            }
+            // ... and the part to the right survives too, with its offset
+            // shifted forward by however much we trimmed off the front.
+            if right_survives {
+                let span = m_end - end;
+                self.mappings.insert(end, MapEntry {
+                    source: mapping.source.clone(),
+                    offset: mapping.offset + (end - m_start),
+                    span,
+                    addr: end,
+                    flags: mapping.flags,
+                    node: node.clone(),
+                });
+                self.try_merge_neighbors(end);
+            }
+
+            // nothing survived: this mapping is gone entirely, so flush
+            // every right off its fork/COW subtree the same way
+            // `unmap_and_propagate` does for a whole-mapping unmap.
+            if !left_survives && !right_survives {
+                Self::flush_rights_recursive(&node, all_rights, &mut affected);
+            }
+
+            // whatever of the mapping actually falls inside [start, end)
+            // goes back to the free list.
+            let freed_start = m_start.max(start);
+            let freed_end = m_end.min(end);
+            self.release_span(freed_start, freed_end - freed_start);
+        }
+
+        affected
+    }
+
+    /// Mark `[start, start + len)` as "don't include in a core dump",
+    /// mirroring Linux's `MADV_DONTDUMP`. Mappings only partially covered
+    /// by the range are split at its edges, same as `unmap`.
+    pub fn madvise_dontdump(&mut self, start: VirtualAddress, len: usize) {
+        self.set_dontdump_over_range(start, len, true);
+    }
+
+    /// Clear the "don't dump" advice over `[start, start + len)`, the
+    /// inverse of `madvise_dontdump` (Linux's `MADV_DODUMP`).
+    pub fn madvise_dodump(&mut self, start: VirtualAddress, len: usize) {
+        self.set_dontdump_over_range(start, len, false);
+    }
+
+    /// Shared implementation of `madvise_dontdump`/`madvise_dodump`: split
+    /// any mapping that straddles an edge of `[start, start + len)`, and set
+    /// the `dontdump` flag to `want` on whatever falls inside it.
+    fn set_dontdump_over_range(&mut self, start: VirtualAddress, len: usize, want: bool) {
+        let end = start + len;
+        let overlapping: Vec<VirtualAddress> = self
+            .mappings
+            .range(..end)
+            .filter(|(_, mapping)| mapping.addr + mapping.span > start)
+            .map(|(&addr, _)| addr)
+            .collect();
+
+        for addr in overlapping {
+            let mapping = self.mappings.remove(&addr).unwrap();
+            let m_start = mapping.addr;
+            let m_end = mapping.addr + mapping.span;
+            let overlap_start = m_start.max(start);
+            let overlap_end = m_end.min(end);
+            // Unlike `unmap`, nothing here is actually being removed —
+            // every fragment is still "the same mapping", just split at
+            // the `dontdump` boundary. None of them is privileged, so every
+            // fragment shares the original node (as `unmap`'s surviving
+            // fragments do), so a `fork()`'d mapping doesn't lose its place
+            // in the COW tree, or rights already revoked through it, just
+            // because part of it got dontdump-toggled.
+            let node = mapping.node;
+
+            if m_start < overlap_start {
+                self.mappings.insert(m_start, MapEntry {
+                    source: mapping.source.clone(),
+                    offset: mapping.offset,
+                    span: overlap_start - m_start,
+                    addr: m_start,
+                    flags: mapping.flags,
+                    node: node.clone(),
+                });
+            }
+
+            let mid_flags = if mapping.flags.dontdump == want {
+                mapping.flags
+            } else {
+                mapping.flags.toggle_dontdump()
+            };
+            self.mappings.insert(overlap_start, MapEntry {
+                source: mapping.source.clone(),
+                offset: mapping.offset + (overlap_start - m_start),
+                span: overlap_end - overlap_start,
+                addr: overlap_start,
+                flags: mid_flags,
+                node: node.clone(),
+            });
+
+            if m_end > overlap_end {
+                self.mappings.insert(overlap_end, MapEntry {
+                    source: mapping.source.clone(),
+                    offset: mapping.offset + (overlap_end - m_start),
+                    span: m_end - overlap_end,
+                    addr: overlap_end,
+                    flags: mapping.flags,
+                    node: node.clone(),
+                });
+            }
+        }
+    }
+
+    /// Enumerate the mappings a crash/core snapshot should capture: every
+    /// readable mapping whose `dontdump` flag is off, so the caller can walk
+    /// exactly the regions that should be captured and skip sensitive or
+    /// huge guard regions. "Readable" is checked against `effective_flags`,
+    /// not the mapping's own `flags`, so a mapping an ancestor has since
+    /// revoked read access from (see `flush_rights`) doesn't get dumped
+    /// just because it was created readable.
+    pub fn dumpable_regions(&self) -> impl Iterator<Item = (VirtualAddress, usize, Arc<dyn DataSource + 'c>, usize)> + '_ {
+        self.mappings
+            .values()
+            .filter(|mapping| mapping.effective_flags().read && !mapping.flags.dontdump)
+            .map(|mapping| (mapping.addr, mapping.span, mapping.source.clone(), mapping.offset))
     }
 
     /// Look up the DataSource and offset within that DataSource by a
@@ -158,19 +792,28 @@ impl<'c> AddressSpace<'c> {
         addr: VirtualAddress,
         access_type: FlagBuilder,
     ) -> Result<(Arc<dyn DataSource + 'c>, usize), &str> {
-        for mapping in &self.mappings {
-            if mapping.addr == addr {
-                // if access_type not one of the flags in mapping.flags. Err
-                if mapping.flags.check_access_perms(access_type) {
-                    return Ok((mapping.source.clone(), mapping.offset)); // lifetime bug! why does returning a cloned &MapEntry require Address Space to outlive static?
-                    // PROBLEM: Address Space, with lifetime 'a, serves a public function that returns an Arc to a Data Source
-                    // Rust is worried that returning the Arc to the Data Source will create a dangling reference.
-                    // dangling reference or double de-allocate?
-                    // CURRENT LIFETIME BOUNDS:
-                    // Map Entry cannot outlive internal Data Source
-                    // Address Space cannot outlive internal Map Entries
-                    // why then it is a problem for a data source to outlive address space?
-                }
+        // find the mapping (if any) whose range `[addr, addr + span)`
+        // contains `addr` — not just one that starts exactly there.
+        let mapping = self
+            .mappings
+            .range(..=addr)
+            .next_back()
+            .map(|(_, mapping)| mapping)
+            .filter(|mapping| addr < mapping.addr + mapping.span);
+        if let Some(mapping) = mapping {
+            // if access_type not one of the flags in mapping.flags. Err
+            // (checked against `effective_flags`, not `flags` directly, so
+            // a rights reduction pushed down from an ancestor via `fork`
+            // actually bites here instead of being dead bookkeeping.)
+            if mapping.effective_flags().check_access_perms(access_type) {
+                return Ok((mapping.source.clone(), mapping.offset + (addr - mapping.addr))); // lifetime bug! why does returning a cloned &MapEntry require Address Space to outlive static?
+                // PROBLEM: Address Space, with lifetime 'a, serves a public function that returns an Arc to a Data Source
+                // Rust is worried that returning the Arc to the Data Source will create a dangling reference.
+                // dangling reference or double de-allocate?
+                // CURRENT LIFETIME BOUNDS:
+                // Map Entry cannot outlive internal Data Source
+                // Address Space cannot outlive internal Map Entries
+                // why then it is a problem for a data source to outlive address space?
             }
         }
         todo!()
@@ -178,12 +821,7 @@ impl<'c> AddressSpace<'c> {
 
     /// Helper function for looking up mappings - I don't use...
     fn get_mapping_for_addr(&self, addr: VirtualAddress) -> Result<&MapEntry, &str> {
-        for (mapping_num, mapping) in (&self.mappings).iter().enumerate() {
-            if mapping_num == addr {
-                return Ok(mapping)
-            }
-        }
-        Err("no mapping found at that address")
+        self.mappings.get(&addr).ok_or("no mapping found at that address")
     }
 }
 
@@ -209,6 +847,7 @@ pub struct FlagBuilder {
     cow: bool,
     private: bool,
     shared: bool,
+    dontdump: bool, // mirrors Linux's MADV_DONTDUMP; orthogonal to every flag above.
 }
 
 impl FlagBuilder {
@@ -226,6 +865,9 @@ impl FlagBuilder {
         if self.cow && self.write { // for COW to work, write needs to be off until after the copy
             return false;
         }
+        // `dontdump` is just advice about core dumps; it doesn't interact
+        // with read/write/execute or private/shared, so there's nothing to
+        // validate about it here.
         return true;
     }
 }
@@ -269,6 +911,7 @@ impl FlagBuilder {
     flag!(cow, toggle_cow);
     flag!(private, toggle_private);
     flag!(shared, toggle_shared);
+    flag!(dontdump, toggle_dontdump);
 
     #[must_use]
     /// Combine two `FlagBuilder`s by boolean or-ing each of their flags.
@@ -290,6 +933,7 @@ impl FlagBuilder {
         let cow = self.cow || other.cow;
         let private = self.private || other.private;
         let shared = self.shared || other.shared;
+        let dontdump = self.dontdump || other.dontdump;
 
         Self {
             read,
@@ -298,6 +942,7 @@ impl FlagBuilder {
             cow,
             private,
             shared,
+            dontdump,
         }
     }
 
@@ -320,6 +965,7 @@ impl FlagBuilder {
         let cow = self.cow && !other.cow;
         let private = self.private && !other.private;
         let shared = self.shared && !other.shared;
+        let dontdump = self.dontdump && !other.dontdump;
 
         Self {
             read,
@@ -328,6 +974,262 @@ impl FlagBuilder {
             cow,
             private,
             shared,
+            dontdump,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `DataSource` that doesn't actually back any bytes; good enough for
+    /// exercising `AddressSpace` bookkeeping, which never reads through it.
+    #[derive(Debug)]
+    struct MockSource;
+    impl DataSource for MockSource {}
+
+    #[test]
+    fn flush_rights_propagates_to_fork_children() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read().and(FlagBuilder::execute());
+        let addr = parent.add_mapping(source, 0, PAGE_SIZE, flags).unwrap();
+
+        let child = parent.fork();
+
+        parent.flush_rights(addr, FlagBuilder::execute()).unwrap();
+
+        // the child's own `flags` are untouched directly by the parent's
+        // flush...
+        let child_mapping = child.mappings.get(&addr).unwrap();
+        assert!(child_mapping.flags.execute);
+        // ...but the revocation propagated down the fork tree means the
+        // child no longer has *effective* access to it.
+        assert!(!child_mapping.effective_flags().execute);
+        // read was never revoked, so it's still reachable the normal way.
+        assert!(child.get_source_for_addr::<MockSource>(addr, FlagBuilder::read()).is_ok());
+    }
+
+    #[test]
+    fn merge_preserves_forked_subtrees() {
+        let source = Arc::new(MockSource);
+        let mut space = AddressSpace::new("space");
+        let flags = FlagBuilder::read();
+
+        let left_addr = PAGE_SIZE * 4;
+        let right_addr = left_addr + PAGE_SIZE;
+        space.mappings.insert(left_addr, MapEntry::new(source.clone(), 0, PAGE_SIZE, left_addr, flags));
+        space.mappings.insert(right_addr, MapEntry::new(source, PAGE_SIZE, PAGE_SIZE, right_addr, flags));
+
+        // simulate each mapping having already been `fork()`'d by hanging a
+        // child node off each one directly.
+        let left_child = MapNode::new(left_addr);
+        let right_child = MapNode::new(right_addr);
+        space.mappings[&left_addr].node.children.lock().unwrap().push(left_child.clone());
+        space.mappings[&right_addr].node.children.lock().unwrap().push(right_child.clone());
+
+        assert!(space.try_merge_neighbors(left_addr));
+
+        let merged = space.mappings.get(&left_addr).unwrap();
+        assert_eq!(merged.span, 2 * PAGE_SIZE);
+        let children = merged.node.children.lock().unwrap();
+        assert!(children.iter().any(|c| Arc::ptr_eq(c, &left_child)));
+        assert!(children.iter().any(|c| Arc::ptr_eq(c, &right_child)));
+    }
+
+    #[test]
+    fn resolve_cow_fault_keeps_already_revoked_rights() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read().and(FlagBuilder::write()).and(FlagBuilder::execute());
+        let addr = parent.add_mapping(source, 0, PAGE_SIZE, flags).unwrap();
+
+        let mut child = parent.fork();
+
+        // the ancestor revokes execute rights while the mapping is still
+        // shared...
+        parent.flush_rights(addr, FlagBuilder::execute()).unwrap();
+        assert!(!child.mappings.get(&addr).unwrap().effective_flags().execute);
+
+        // ...then the child takes its COW write fault. The fresh node it
+        // gets detaches it from *future* flushes (reasonable — it's its own
+        // copy now), but must not silently restore what was already taken
+        // away.
+        child.resolve_cow_fault(addr).unwrap();
+        assert!(!child.mappings.get(&addr).unwrap().effective_flags().execute);
+        // read/write were never revoked.
+        assert!(child.mappings.get(&addr).unwrap().effective_flags().read);
+    }
+
+    #[test]
+    fn unmap_flushes_rights_for_fully_removed_forked_mappings() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read();
+        let addr = parent.add_mapping(source, 0, PAGE_SIZE, flags).unwrap();
+
+        let child = parent.fork();
+        assert!(child.mappings.get(&addr).unwrap().effective_flags().read);
+
+        // the whole mapping falls inside the unmapped range, so it's
+        // removed outright rather than trimmed.
+        let affected = parent.unmap(addr, PAGE_SIZE);
+        assert_eq!(affected, vec![addr]);
+        assert!(!child.mappings.get(&addr).unwrap().effective_flags().read);
+    }
+
+    #[test]
+    fn unmap_hole_punch_keeps_both_fragments_linked_to_the_forked_node() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read();
+        let addr = parent.add_mapping(source, 0, 3 * PAGE_SIZE, flags).unwrap();
+
+        let child = parent.fork();
+        assert!(child.mappings.get(&addr).unwrap().effective_flags().read);
+
+        // punch a hole in the middle page, leaving both the left and right
+        // thirds of the original mapping behind as separate fragments.
+        let left_addr = addr;
+        let right_addr = addr + 2 * PAGE_SIZE;
+        let affected = parent.unmap(addr + PAGE_SIZE, PAGE_SIZE);
+        assert!(affected.is_empty());
+        assert!(parent.mappings.contains_key(&left_addr));
+        assert!(parent.mappings.contains_key(&right_addr));
+
+        // a flush through either surviving fragment must still reach the
+        // child's single (unsplit) mapping over the original full range —
+        // neither fragment is allowed to be the orphaned one.
+        parent.flush_rights(left_addr, FlagBuilder::read()).unwrap();
+        assert!(!child.mappings.get(&addr).unwrap().effective_flags().read);
+    }
+
+    #[test]
+    fn dumpable_regions_respects_revoked_read_rights() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read();
+        let addr = parent.add_mapping(source, 0, PAGE_SIZE, flags).unwrap();
+
+        let child = parent.fork();
+        assert_eq!(child.dumpable_regions().count(), 1);
+
+        // once the parent revokes read access, the child's copy is no
+        // longer one `get_source_for_addr` would allow a read through, so
+        // a crash snapshot shouldn't capture it either.
+        parent.flush_rights(addr, FlagBuilder::read()).unwrap();
+        assert_eq!(child.dumpable_regions().count(), 0);
+    }
+
+    #[test]
+    fn with_reuse_seed_is_deterministic() {
+        fn run(seed: u64) -> Vec<VirtualAddress> {
+            let source = Arc::new(MockSource);
+            let mut space = AddressSpace::with_reuse_seed("space", seed);
+            let mut addrs = Vec::new();
+            for _ in 0..8 {
+                let addr = space.add_mapping(source.clone(), 0, PAGE_SIZE, FlagBuilder::read()).unwrap();
+                addrs.push(addr);
+                space.remove_mapping(source.clone(), addr).unwrap();
+            }
+            addrs
+        }
+
+        // same seed, same sequence of add/remove calls -> same addresses
+        // every time, which is the entire point of `with_reuse_seed`.
+        assert_eq!(run(7), run(7));
+    }
+
+    #[test]
+    fn reuse_pool_sometimes_hands_back_a_freed_range() {
+        let source = Arc::new(MockSource);
+        let mut space = AddressSpace::with_reuse_seed("space", 7);
+        let mut addrs = Vec::new();
+        for _ in 0..32 {
+            let addr = space.add_mapping(source.clone(), 0, PAGE_SIZE, FlagBuilder::read()).unwrap();
+            addrs.push(addr);
+            space.remove_mapping(source.clone(), addr).unwrap();
+        }
+        // without quarantining, every one of these would be the same
+        // first-fit address (the free list coalesces as fast as it frees);
+        // the pool spreads them out, but still hands a freed range back
+        // some of the time, so at least some of them repeat.
+        let distinct: std::collections::HashSet<_> = addrs.iter().collect();
+        assert!(distinct.len() < addrs.len());
+    }
+
+    #[test]
+    fn merge_redirects_ancestor_reference_to_merged_node() {
+        let source = Arc::new(MockSource);
+        let mut parent = AddressSpace::new("parent");
+        let flags = FlagBuilder::read();
+        let addr = parent.add_mapping(source.clone(), 0, PAGE_SIZE, flags).unwrap();
+
+        let mut child = parent.fork();
+
+        // Splice in a new mapping as the forked one's left neighbor, the
+        // same way a real `add_mapping`-then-auto-merge would, so the
+        // forked mapping ends up as `merge_into`'s "right" side. (We insert
+        // directly rather than going through `add_mapping_at`, which would
+        // insist on its own guard page against the already-forked mapping.)
+        let left_addr = addr - PAGE_SIZE;
+        child.mappings.insert(left_addr, MapEntry::new(source, 0, PAGE_SIZE, left_addr, flags));
+        child.mappings.get_mut(&addr).unwrap().offset = PAGE_SIZE;
+        assert!(child.try_merge_neighbors(left_addr));
+
+        // the parent only ever knew about the original (now-merged-away)
+        // node; flushing rights through it must still reach the child's
+        // merged mapping instead of silently hitting an orphaned node.
+        parent.flush_rights(addr, FlagBuilder::read()).unwrap();
+        assert!(!child.mappings.get(&left_addr).unwrap().effective_flags().read);
+    }
+
+    #[test]
+    fn add_mapping_at_requires_a_leading_guard_page() {
+        let source = Arc::new(MockSource);
+        let mut space = AddressSpace::new("space");
+        let flags = FlagBuilder::read();
+        let first = space.add_mapping(source.clone(), 0, PAGE_SIZE, flags).unwrap();
+
+        // placing a second mapping flush against the end of the first, with
+        // no guard page between them, must be rejected.
+        let flush_start = first + PAGE_SIZE;
+        assert!(space.add_mapping_at(source, 0, PAGE_SIZE, flush_start, flags).is_err());
+    }
+
+    #[test]
+    fn add_mapping_is_first_fit_and_splits_the_free_span() {
+        let source = Arc::new(MockSource);
+        let mut space = AddressSpace::new("space");
+        let flags = FlagBuilder::read();
+
+        // the whole address space starts as a single free span; first-fit
+        // should hand out the lowest address in it (past the guard page)
+        // and leave the rest split around the new mapping.
+        let addr = space.add_mapping(source, 0, PAGE_SIZE, flags).unwrap();
+        assert_eq!(addr, 2 * PAGE_SIZE);
+
+        let mut expected = BTreeMap::new();
+        expected.insert(PAGE_SIZE, PAGE_SIZE);
+        expected.insert(3 * PAGE_SIZE, VADDR_MAX - 3 * PAGE_SIZE);
+        assert_eq!(space.free_list, expected);
+    }
+
+    #[test]
+    fn remove_mapping_coalesces_the_free_list() {
+        let source = Arc::new(MockSource);
+        let mut space = AddressSpace::new("space");
+        let flags = FlagBuilder::read();
+
+        let addr = space.add_mapping(source.clone(), 0, PAGE_SIZE, flags).unwrap();
+        assert_eq!(space.free_list.len(), 2);
+
+        // freeing the mapping should merge it back into both of its
+        // surviving neighbors, landing back at the original single span.
+        space.remove_mapping(source, addr).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(PAGE_SIZE, VADDR_MAX - PAGE_SIZE);
+        assert_eq!(space.free_list, expected);
+    }
+}